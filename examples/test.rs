@@ -1,8 +1,8 @@
 extern crate signals;
 
 fn main() {
-    let sigs = signals::Signals::new().unwrap();
-    sigs.subscribe(signals::Signal::TermStop);
+    let sigs = signals::Signals::new();
+    sigs.subscribe(signals::Signal::TermStop).unwrap();
     for s in sigs.receiver().iter() {
         println!("{:?}", s);
     }
@@ -6,157 +6,569 @@
 //! # Example
 //!
 //! ```rust
-//! let signals = Signals::new().unwrap();
-//! signals.subscribe(Interrupt);
+//! let signals = Signals::new();
+//! signals.subscribe(Interrupt).unwrap();
 //! for s in signals.receiver().iter() {
 //!     println!("{:?}", s);
 //! }
 //! ```
 //!
-//! At any given time there can only be one signal handler in the program.
-//! `Signals::new()` returns `None` if there is already another signal handler.
+//! Every call to `Signals::new()` yields an independent handle with its own
+//! channel. Several handles may subscribe to the same signal at once: the
+//! underlying C-level handler is installed only once per signal number
+//! (reference-counted across handles) and fans the decoded signal out to
+//! every handle subscribed to it, so unrelated subsystems in the same
+//! process can each listen without stepping on each other.
 #![crate_type = "lib"]
 
 #[allow(unstable)]
 extern crate libc;
 
-use self::libc::{c_int};
+use self::libc::{c_int, c_void, size_t, ssize_t, pid_t, uid_t};
 use std::sync::mpsc::{channel, Receiver, Sender};
-use std::sync::{Once, ONCE_INIT};
-use std::sync::atomic::{AtomicBool, ATOMIC_BOOL_INIT};
-use std::sync::atomic::Ordering::Relaxed;
-use std::mem::{forget, transmute};
+use std::sync::{Arc, Mutex, Once, ONCE_INIT};
+use std::sync::atomic::{AtomicBool, AtomicIsize, AtomicUsize, ATOMIC_ISIZE_INIT, ATOMIC_USIZE_INIT};
+use std::sync::atomic::Ordering::{Relaxed, SeqCst};
+use std::collections::HashMap;
+use std::mem::{forget, transmute, zeroed, size_of};
+use std::ptr;
+use std::thread;
+use std::io;
+use std::time::{Duration, Instant};
 
-static mut ALIVE: AtomicBool = ATOMIC_BOOL_INIT;
 static mut INITIALIZED: Once = ONCE_INIT;
-static mut SND: *const Sender<Signal> = 0 as *const Sender<Signal>;
-static mut RCV: *const Receiver<Signal> = 0 as *const Receiver<Signal>;
+
+// Next id handed out to a `Signals` handle, used to find and remove exactly
+// that handle's entries in `SUBSCRIBERS` again (a `Sender` has no useful
+// identity of its own to filter on).
+static mut NEXT_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+// Write end of the self-pipe, stashed where the async-signal-safe `handler` can
+// reach it without touching a lock. `handler` may do nothing but write a
+// fixed-size `RawMsg` record here; everything else happens on the reader
+// thread spawned in `Signals::new()`.
+static mut WRITE_FD: AtomicIsize = ATOMIC_ISIZE_INIT;
+
+// The `sigaction` that was installed before we subscribed, keyed by signal
+// number, so the last subscriber leaving can restore it instead of always
+// falling back to `SIG_DFL`. This is what lets handlers installed elsewhere
+// in the process compose with ours.
+static mut OLD_ACTIONS: *const Mutex<HashMap<c_int, sigaction_t>> =
+    0 as *const Mutex<HashMap<c_int, sigaction_t>>;
+
+// The `sigaction` we currently have installed for a signal, keyed by raw
+// signal number. Unlike `OLD_ACTIONS` (the action to restore once the last
+// subscriber leaves), this tracks what later `subscribe_with` calls for the
+// same signal need to broaden rather than silently ignore: e.g. a later
+// subscriber asking for `origin: true` upgrades an already-installed plain
+// handler to `SA_SIGINFO` for every subscriber of that signal.
+static mut INSTALLED: *const Mutex<HashMap<c_int, sigaction_t>> =
+    0 as *const Mutex<HashMap<c_int, sigaction_t>>;
+
+// Subscriber channels, keyed by raw signal number, that a delivered signal is
+// fanned out to. The C-level handler for a given signal number is installed
+// only once, the first time any handle subscribes to it; it is removed again
+// once the last subscriber for that signal goes away (see `Drop`).
+static mut SUBSCRIBERS: *const Mutex<HashMap<c_int, Vec<Subscriber>>> =
+    0 as *const Mutex<HashMap<c_int, Vec<Subscriber>>>;
+
+// Flags registered via `subscribe_flag`, keyed by raw signal number, each
+// tagged with the id of the handle that registered it so `remove_subscription`
+// can drop exactly this handle's flags again (mirrors `Subscriber::id` below).
+// Several flags (and ordinary channel delivery) can coexist for the same signal.
+static mut FLAGS: *const Mutex<HashMap<c_int, Vec<(usize, Arc<AtomicBool>)>>> =
+    0 as *const Mutex<HashMap<c_int, Vec<(usize, Arc<AtomicBool>)>>>;
 
 extern {
-    fn signal(signum: c_int, hdlr: Option<unsafe extern fn(c_int)>);
+    fn pipe2(fds: *mut c_int, flags: c_int) -> c_int;
+    fn pipe(fds: *mut c_int) -> c_int;
+    fn fcntl(fd: c_int, cmd: c_int, ...) -> c_int;
+    fn write(fd: c_int, buf: *const c_void, count: size_t) -> ssize_t;
+    fn read(fd: c_int, buf: *mut c_void, count: size_t) -> ssize_t;
+    fn __errno_location() -> *mut c_int;
+    fn sigaction(signum: c_int, act: *const sigaction_t, oldact: *mut sigaction_t) -> c_int;
+    fn sigemptyset(set: *mut sigset_t) -> c_int;
+    fn sigaddset(set: *mut sigset_t, signum: c_int) -> c_int;
+    fn pthread_sigmask(how: c_int, set: *const sigset_t, oldset: *mut sigset_t) -> c_int;
+    fn sigwait(set: *const sigset_t, sig: *mut c_int) -> c_int;
+    fn sigtimedwait(set: *const sigset_t, info: *mut siginfo_t, timeout: *const timespec_t) -> c_int;
+    fn __libc_current_sigrtmin() -> c_int;
+    fn __libc_current_sigrtmax() -> c_int;
+}
+
+/// First real-time signal number available on this system (glibc reserves a
+/// few of the kernel's `SIGRTMIN..SIGRTMAX` range for its own use).
+fn sigrtmin() -> c_int {
+    unsafe { __libc_current_sigrtmin() }
+}
+
+/// Last real-time signal number available on this system.
+fn sigrtmax() -> c_int {
+    unsafe { __libc_current_sigrtmax() }
+}
+
+const O_NONBLOCK: c_int = 0o4000;
+const O_CLOEXEC: c_int = 0o2000000;
+const F_GETFD: c_int = 1;
+const F_SETFD: c_int = 2;
+const F_GETFL: c_int = 3;
+const F_SETFL: c_int = 4;
+const FD_CLOEXEC: c_int = 1;
+
+const SA_NOCLDSTOP: c_int = 1;
+const SA_SIGINFO: c_int = 4;
+const SA_RESTART: c_int = 0x10000000;
+
+const SIG_BLOCK: c_int = 0;
+const SIG_SETMASK: c_int = 2;
+const EINTR: c_int = 4;
+const EAGAIN: c_int = 11;
+
+// glibc's sigset_t is 1024 bits wide.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct sigset_t {
+    bits: [u64; 16],
+}
+
+// Layout of glibc's `struct sigaction` on Linux (sa_handler/sa_mask/sa_flags/
+// sa_restorer, in that order). `sa_handler` is stored as a raw `usize` since
+// it's either a plain `fn(c_int)` or, when `SA_SIGINFO` is set, a
+// `fn(c_int, *mut siginfo_t, *mut c_void)` (the same union member in C).
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct sigaction_t {
+    sa_handler: usize,
+    sa_mask: sigset_t,
+    sa_flags: c_int,
+    sa_restorer: usize,
+}
+
+// `siginfo_t` is a large union in glibc; we only ever read the `si_pid`/
+// `si_uid` fields that are common to every variant generated by `kill(2)`,
+// `sigqueue(2)`, etc., so we only model the common prefix (si_signo/si_errno/
+// si_code followed by si_pid/si_uid) rather than the whole union.
+#[repr(C)]
+struct siginfo_t {
+    si_signo: c_int,
+    si_errno: c_int,
+    si_code: c_int,
+    _pad: c_int,
+    si_pid: pid_t,
+    si_uid: uid_t,
+}
+
+// `struct timespec` on Linux x86_64: both fields are a 64-bit `long`.
+#[repr(C)]
+struct timespec_t {
+    tv_sec: i64,
+    tv_nsec: i64,
+}
+
+/// Record written to the self-pipe by either handler variant. Fixed size and
+/// plain-old-data, so a single `write()` of it is atomic (well under `PIPE_BUF`)
+/// and therefore async-signal-safe.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct RawMsg {
+    signo: c_int,
+    has_pid: u8,
+    pid: pid_t,
+    has_uid: u8,
+    uid: uid_t,
+}
+
+unsafe fn errno() -> c_int {
+    *__errno_location()
+}
+
+unsafe fn set_errno(e: c_int) {
+    *__errno_location() = e;
+}
+
+/// Create the self-pipe, preferring `pipe2` and falling back to `pipe` plus
+/// `fcntl` on systems where `pipe2` isn't available. Returns `(read_fd, write_fd)`.
+///
+/// Only the write end is non-blocking, so the signal handler's `write()`
+/// never blocks; the read end stays blocking, so the reader thread sleeps in
+/// `read()` instead of spinning when the pipe is empty.
+unsafe fn make_self_pipe() -> (c_int, c_int) {
+    let mut fds: [c_int; 2] = [-1, -1];
+    if pipe2(fds.as_mut_ptr(), O_NONBLOCK | O_CLOEXEC) == 0 {
+        // pipe2 can only apply its flags to both ends at once; clear
+        // O_NONBLOCK again on the read end.
+        let flags = fcntl(fds[0], F_GETFL);
+        fcntl(fds[0], F_SETFL, flags & !O_NONBLOCK);
+        return (fds[0], fds[1]);
+    }
+    if pipe(fds.as_mut_ptr()) != 0 {
+        panic!("signals: could not create self-pipe");
+    }
+    for &fd in fds.iter() {
+        let fdflags = fcntl(fd, F_GETFD);
+        fcntl(fd, F_SETFD, fdflags | FD_CLOEXEC);
+    }
+    let flags = fcntl(fds[1], F_GETFL);
+    fcntl(fds[1], F_SETFL, flags | O_NONBLOCK);
+    (fds[0], fds[1])
+}
+
+// The only thing a handler is allowed to do is write a fixed-size record to
+// the self-pipe: everything else (allocating, taking locks, sending on an
+// mpsc channel) is not async-signal-safe and may deadlock or corrupt state if
+// a signal arrives while the interrupted code held the same lock.
+unsafe fn write_msg(msg: RawMsg) {
+    let saved_errno = errno();
+    write(WRITE_FD.load(SeqCst) as c_int, &msg as *const RawMsg as *const c_void, size_of::<RawMsg>() as size_t);
+    set_errno(saved_errno);
 }
 
 unsafe extern fn handler(num: c_int) {
-    if !ALIVE.load(Relaxed) {
+    write_msg(RawMsg { signo: num, has_pid: 0, pid: 0, has_uid: 0, uid: 0 });
+}
+
+// For synchronous, kernel-raised signals (faults), `si_pid`/`si_uid` aren't
+// populated by the kernel and are meaningless to report.
+fn has_origin_info(num: c_int) -> bool {
+    !(num == signal_to_raw(Signal::Segfault)
+        || num == signal_to_raw(Signal::Bus)
+        || num == signal_to_raw(Signal::FPE)
+        || num == signal_to_raw(Signal::Illegal)
+        || num == signal_to_raw(Signal::Trap)
+        || num == signal_to_raw(Signal::Sys))
+}
+
+// Registered with `SA_SIGINFO` for subscriptions that opted into origin
+// tracking. Reads `si_pid`/`si_uid` out of the `siginfo_t` the kernel hands
+// us; both of those reads are async-signal-safe (plain memory loads).
+unsafe extern fn handler_siginfo(num: c_int, info: *mut siginfo_t, _ctx: *mut c_void) {
+    if info.is_null() || !has_origin_info(num) {
+        write_msg(RawMsg { signo: num, has_pid: 0, pid: 0, has_uid: 0, uid: 0 });
         return;
     }
-    let snd: &Sender<Signal> = transmute(SND);
+    let pid = (*info).si_pid;
+    let uid = (*info).si_uid;
+    write_msg(RawMsg { signo: num, has_pid: 1, pid: pid, has_uid: 1, uid: uid });
+}
+
+/// Decode a raw signal number, as written to the self-pipe, back into a
+/// `Signal`. Real-time signals are recognized by falling within
+/// `SIGRTMIN()..=SIGRTMAX()` and decoded to their offset from `SIGRTMIN()`
+/// rather than compared against a fixed discriminant.
+fn signal_from_num(num: c_int) -> Option<Signal> {
+    if num >= sigrtmin() && num <= sigrtmax() {
+        return Some(Signal::RealTime((num - sigrtmin()) as u8));
+    }
     match num {
-        _ if num == Signal::Abort     as c_int => snd.send(Signal::Abort),
-        _ if num == Signal::Alarm     as c_int => snd.send(Signal::Alarm),
-        _ if num == Signal::Bus       as c_int => snd.send(Signal::Bus),
-        _ if num == Signal::Child     as c_int => snd.send(Signal::Child),
-        _ if num == Signal::Continue  as c_int => snd.send(Signal::Continue),
-        _ if num == Signal::FPE       as c_int => snd.send(Signal::FPE),
-        _ if num == Signal::Hangup    as c_int => snd.send(Signal::Hangup),
-        _ if num == Signal::Illegal   as c_int => snd.send(Signal::Illegal),
-        _ if num == Signal::Interrupt as c_int => snd.send(Signal::Interrupt),
-        _ if num == Signal::Kill      as c_int => snd.send(Signal::Kill),
-        _ if num == Signal::Pipe      as c_int => snd.send(Signal::Pipe),
-        _ if num == Signal::Quit      as c_int => snd.send(Signal::Quit),
-        _ if num == Signal::Poll      as c_int => snd.send(Signal::Poll),
-        _ if num == Signal::Prof      as c_int => snd.send(Signal::Prof),
-        _ if num == Signal::Segfault  as c_int => snd.send(Signal::Segfault),
-        _ if num == Signal::Stop      as c_int => snd.send(Signal::Stop),
-        _ if num == Signal::TermStop  as c_int => snd.send(Signal::TermStop),
-        _ if num == Signal::Sys       as c_int => snd.send(Signal::Sys),
-        _ if num == Signal::Terminate as c_int => snd.send(Signal::Terminate),
-        _ if num == Signal::Trap      as c_int => snd.send(Signal::Trap),
-        _ if num == Signal::TTIN      as c_int => snd.send(Signal::TTIN),
-        _ if num == Signal::TTOU      as c_int => snd.send(Signal::TTOU),
-        _ if num == Signal::Urgent    as c_int => snd.send(Signal::Urgent),
-        _ if num == Signal::User1     as c_int => snd.send(Signal::User1),
-        _ if num == Signal::User2     as c_int => snd.send(Signal::User2),
-        _ if num == Signal::WinSize   as c_int => snd.send(Signal::WinSize),
-        _ if num == Signal::XCPU      as c_int => snd.send(Signal::XCPU),
-        _ if num == Signal::XFSZ      as c_int => snd.send(Signal::XFSZ),
-        _ => Ok(()),
-    }.unwrap_or_else(|_| ());
+        _ if num == signal_to_raw(Signal::Abort)     => Some(Signal::Abort),
+        _ if num == signal_to_raw(Signal::Alarm)     => Some(Signal::Alarm),
+        _ if num == signal_to_raw(Signal::Bus)       => Some(Signal::Bus),
+        _ if num == signal_to_raw(Signal::Child)     => Some(Signal::Child),
+        _ if num == signal_to_raw(Signal::Continue)  => Some(Signal::Continue),
+        _ if num == signal_to_raw(Signal::FPE)       => Some(Signal::FPE),
+        _ if num == signal_to_raw(Signal::Hangup)    => Some(Signal::Hangup),
+        _ if num == signal_to_raw(Signal::Illegal)   => Some(Signal::Illegal),
+        _ if num == signal_to_raw(Signal::Interrupt) => Some(Signal::Interrupt),
+        _ if num == signal_to_raw(Signal::Kill)      => Some(Signal::Kill),
+        _ if num == signal_to_raw(Signal::Pipe)      => Some(Signal::Pipe),
+        _ if num == signal_to_raw(Signal::Quit)      => Some(Signal::Quit),
+        _ if num == signal_to_raw(Signal::Poll)      => Some(Signal::Poll),
+        _ if num == signal_to_raw(Signal::Prof)      => Some(Signal::Prof),
+        _ if num == signal_to_raw(Signal::Segfault)  => Some(Signal::Segfault),
+        _ if num == signal_to_raw(Signal::Stop)      => Some(Signal::Stop),
+        _ if num == signal_to_raw(Signal::TermStop)  => Some(Signal::TermStop),
+        _ if num == signal_to_raw(Signal::Sys)       => Some(Signal::Sys),
+        _ if num == signal_to_raw(Signal::Terminate) => Some(Signal::Terminate),
+        _ if num == signal_to_raw(Signal::Trap)      => Some(Signal::Trap),
+        _ if num == signal_to_raw(Signal::TTIN)      => Some(Signal::TTIN),
+        _ if num == signal_to_raw(Signal::TTOU)      => Some(Signal::TTOU),
+        _ if num == signal_to_raw(Signal::Urgent)    => Some(Signal::Urgent),
+        _ if num == signal_to_raw(Signal::User1)     => Some(Signal::User1),
+        _ if num == signal_to_raw(Signal::User2)     => Some(Signal::User2),
+        _ if num == signal_to_raw(Signal::WinSize)   => Some(Signal::WinSize),
+        _ if num == signal_to_raw(Signal::XCPU)      => Some(Signal::XCPU),
+        _ if num == signal_to_raw(Signal::XFSZ)      => Some(Signal::XFSZ),
+        _ => None,
+    }
+}
+
+/// Who sent a signal, as reported by the kernel via `SA_SIGINFO`. `pid`/`uid`
+/// are `None` for signals that aren't sent with `kill`-like semantics (e.g.
+/// `Segfault`) or when the handler wasn't registered with origin tracking.
+#[derive(Copy, Clone, Debug)]
+pub struct Origin {
+    pub signal: Signal,
+    pub pid: Option<pid_t>,
+    pub uid: Option<uid_t>,
+}
+
+// One handle's registration for a given signal number: its id (so `Drop` can
+// find and remove exactly this entry again), and the sender for whichever
+// channel this particular subscription actually reads, if any. A channel
+// subscription only ever feeds one of `receiver()`/`origin_receiver()` for a
+// given signal, matching whether it asked for `origin: true`, so the other
+// channel doesn't silently fill up with messages nobody drains. `sink` is
+// `None` for a `subscribe_flag`-only registration, which exists purely to
+// keep the handler installed/reference-counted and delivers through `FLAGS`
+// instead, never through a channel.
+struct Subscriber {
+    id: usize,
+    sink: Option<Sink>,
+}
+
+enum Sink {
+    Signal(Sender<Signal>),
+    Origin(Sender<Origin>),
+}
+
+/// Background reader: loop on `read()` of the self-pipe's read end, decode
+/// each fixed-size record and fan it out to every handle subscribed to that
+/// signal number, plus any flags registered for it.
+fn read_loop(read_fd: c_int) {
+    let mut msg: RawMsg = unsafe { zeroed() };
+    let msg_size = size_of::<RawMsg>();
+    loop {
+        let n = unsafe { read(read_fd, &mut msg as *mut RawMsg as *mut c_void, msg_size as size_t) };
+        if n < 0 {
+            // Most likely EINTR (a signal landing on this thread); the write
+            // end is never closed while the process is alive, so just retry.
+            continue;
+        }
+        if n as usize != msg_size {
+            continue;
+        }
+        if let Some(sig) = signal_from_num(msg.signo) {
+            let origin = Origin {
+                signal: sig,
+                pid: if msg.has_pid != 0 { Some(msg.pid) } else { None },
+                uid: if msg.has_uid != 0 { Some(msg.uid) } else { None },
+            };
+            let registry = unsafe { subscribers() };
+            if let Some(subscribed) = registry.lock().unwrap().get(&msg.signo) {
+                for sub in subscribed.iter() {
+                    match sub.sink {
+                        Some(Sink::Signal(ref snd)) => { let _ = snd.send(sig); }
+                        Some(Sink::Origin(ref origin_snd)) => { let _ = origin_snd.send(origin); }
+                        None => {}
+                    }
+                }
+            }
+            let flags = unsafe { flags_registry() };
+            if let Some(registered) = flags.lock().unwrap().get(&msg.signo) {
+                for &(_, ref flag) in registered.iter() {
+                    flag.store(true, Relaxed);
+                }
+            }
+        }
+    }
 }
 
 /// Available signals.
-#[derive(Copy, Debug)]
+///
+/// The classic signals below always map to the same numbers on Linux; the
+/// raw number behind each is given by `signal_to_raw` rather than an enum
+/// discriminant, since the addition of `RealTime` (whose number is only known
+/// at runtime, via `SIGRTMIN`/`SIGRTMAX`) makes this enum no longer fieldless.
+#[derive(Copy, Clone, Debug)]
 pub enum Signal {
     /// Process abort signal
-    Abort     = 6,
+    Abort,
     /// Alarm clock
-    Alarm     = 14,
+    Alarm,
     /// Access to an undefined portion of a memory object
-    Bus       = 10,
+    Bus,
     /// Child process terminated, stopped,
-    Child     = 18,
+    Child,
     /// Continue executing, if stopped.
-    Continue  = 25,
+    Continue,
     /// Erroneous arithmetic operation.
-    FPE       = 8,
+    FPE,
     /// Hangup.
-    Hangup    = 1,
+    Hangup,
     /// Illegal instruction.
-    Illegal   = 4,
+    Illegal,
     /// Terminal interrupt signal.
-    Interrupt = 2,
+    Interrupt,
     /// Kill (cannot be caught or ignored).
-    Kill      = 9,
+    Kill,
     /// Abnormal termination of the process	Write on a pipe with no one to read it.
-    Pipe      = 13,
+    Pipe,
     /// Abnormal termination of the process	Terminal quit signal.
-    Quit      = 3,
+    Quit,
     /// Pollable event.
-    Poll      = 22,
+    Poll,
     /// Profiling timer expired.
-    Prof      = 29,
+    Prof,
     /// Invalid memory reference.
-    Segfault  = 11,
+    Segfault,
     /// Stop executing (cannot be caught or ignored).
-    Stop      = 23,
+    Stop,
     /// Terminal stop signal.
-    TermStop  = 20,
+    TermStop,
     /// Bad system call.
-    Sys       = 12,
+    Sys,
     /// Termination signal.
-    Terminate = 15,
+    Terminate,
     /// Trace/breakpoint trap.
-    Trap      = 5,
+    Trap,
     /// Background process attempting read.
-    TTIN      = 26,
+    TTIN,
     /// Background process attempting write.
-    TTOU      = 27,
+    TTOU,
     /// High bandwidth data is available at a socket.
-    Urgent    = 21,
+    Urgent,
     /// User-defined signal 1.
-    User1     = 16,
+    User1,
     /// User-defined signal 2.
-    User2     = 17,
+    User2,
     /// Window resized.
-    WinSize   = 28,
+    WinSize,
     /// CPU time limit exceeded.
-    XCPU      = 30,
+    XCPU,
     /// File size limit exceeded.
-    XFSZ      = 31,
+    XFSZ,
+    /// A POSIX real-time signal, `SIGRTMIN() + n`. Validated against
+    /// `SIGRTMAX()` when subscribing. Unlike the classic signals above,
+    /// real-time signals are queued by the kernel rather than coalesced, so
+    /// each delivery produces its own message on the channel, preserving
+    /// both order and count (up to the self-pipe's buffering capacity).
+    RealTime(u8),
+}
+
+/// The raw signal number for `sig`, i.e. what `kill(2)`/`sigaction(2)` expect.
+fn signal_to_raw(sig: Signal) -> c_int {
+    match sig {
+        Signal::Abort     => 6,
+        Signal::Alarm     => 14,
+        Signal::Bus       => 10,
+        Signal::Child     => 18,
+        Signal::Continue  => 25,
+        Signal::FPE       => 8,
+        Signal::Hangup    => 1,
+        Signal::Illegal   => 4,
+        Signal::Interrupt => 2,
+        Signal::Kill      => 9,
+        Signal::Pipe      => 13,
+        Signal::Quit      => 3,
+        Signal::Poll      => 22,
+        Signal::Prof      => 29,
+        Signal::Segfault  => 11,
+        Signal::Stop      => 23,
+        Signal::TermStop  => 20,
+        Signal::Sys       => 12,
+        Signal::Terminate => 15,
+        Signal::Trap      => 5,
+        Signal::TTIN      => 26,
+        Signal::TTOU      => 27,
+        Signal::Urgent    => 21,
+        Signal::User1     => 16,
+        Signal::User2     => 17,
+        Signal::WinSize   => 28,
+        Signal::XCPU      => 30,
+        Signal::XFSZ      => 31,
+        Signal::RealTime(n) => sigrtmin() + n as c_int,
+    }
+}
+
+/// Options controlling how a signal is installed with `sigaction`.
+///
+/// `SigActionFlags::new()` returns the defaults used by `subscribe`: `restart`
+/// on, nothing blocked during handler execution.
+pub struct SigActionFlags {
+    /// Set `SA_RESTART`, so that slow syscalls interrupted by this signal are
+    /// automatically resumed instead of failing with `EINTR`.
+    pub restart: bool,
+    /// Set `SA_NOCLDSTOP`. Only meaningful for `Signal::Child`: suppresses
+    /// notification when a child is merely stopped rather than terminated.
+    pub no_child_stop: bool,
+    /// Signals to block for the duration of the handler, in addition to the
+    /// one being handled.
+    pub block: Vec<Signal>,
+    /// Register with `SA_SIGINFO` so the sending PID/UID are captured and
+    /// delivered through `Signals::origin_receiver()`.
+    pub origin: bool,
+}
+
+impl SigActionFlags {
+    /// The defaults used by `subscribe`: `SA_RESTART` on, nothing else set.
+    pub fn new() -> SigActionFlags {
+        SigActionFlags {
+            restart: true,
+            no_child_stop: false,
+            block: Vec::new(),
+            origin: false,
+        }
+    }
+}
+
+unsafe fn old_actions() -> &'static Mutex<HashMap<c_int, sigaction_t>> {
+    transmute(OLD_ACTIONS)
+}
+
+unsafe fn installed_actions() -> &'static Mutex<HashMap<c_int, sigaction_t>> {
+    transmute(INSTALLED)
+}
+
+unsafe fn subscribers() -> &'static Mutex<HashMap<c_int, Vec<Subscriber>>> {
+    transmute(SUBSCRIBERS)
+}
+
+unsafe fn flags_registry() -> &'static Mutex<HashMap<c_int, Vec<(usize, Arc<AtomicBool>)>>> {
+    transmute(FLAGS)
 }
 
 /// Signal handler
-pub struct Signals;
+///
+/// An independent handle with its own `Receiver<Signal>`/`Receiver<Origin>`.
+/// Several handles, in the same or different parts of a process, may
+/// subscribe to the same signal at once; each receives its own copy.
+pub struct Signals {
+    id: usize,
+    snd: Sender<Signal>,
+    origin_snd: Sender<Origin>,
+    rcv: Receiver<Signal>,
+    origin_rcv: Receiver<Origin>,
+    // Raw signal numbers this handle is currently subscribed to, so `Drop`
+    // knows what to remove itself from.
+    subscribed: Mutex<Vec<c_int>>,
+}
 
 impl Signals {
-    /// Create a new signal handler
-    ///
-    /// Returns `None` if there is already another signal handler in the program.
-    pub fn new() -> Option<Signals> {
+    /// Create a new, independent signal handler.
+    pub fn new() -> Signals {
         unsafe {
             INITIALIZED.call_once(|| {
-                let (s, r) = channel();
-                let s = Box::new(s);
-                let r = Box::new(r);
-                SND = &*s as *const _;
-                RCV = &*r as *const _;
-                forget(s);
-                forget(r);
+                let (read_fd, write_fd) = make_self_pipe();
+                WRITE_FD.store(write_fd as isize, SeqCst);
+                thread::spawn(move || read_loop(read_fd));
+
+                let old_actions = Box::new(Mutex::new(HashMap::new()));
+                OLD_ACTIONS = &*old_actions as *const _;
+                forget(old_actions);
+
+                let installed = Box::new(Mutex::new(HashMap::new()));
+                INSTALLED = &*installed as *const _;
+                forget(installed);
+
+                let subscribers = Box::new(Mutex::new(HashMap::new()));
+                SUBSCRIBERS = &*subscribers as *const _;
+                forget(subscribers);
+
+                let flags = Box::new(Mutex::new(HashMap::new()));
+                FLAGS = &*flags as *const _;
+                forget(flags);
             });
-            if ALIVE.compare_and_swap(false, true, Relaxed) {
-                return None;
+
+            let (s, r) = channel();
+            let (origin_s, origin_r) = channel();
+            Signals {
+                id: NEXT_ID.fetch_add(1, SeqCst),
+                snd: s,
+                origin_snd: origin_s,
+                rcv: r,
+                origin_rcv: origin_r,
+                subscribed: Mutex::new(Vec::new()),
             }
-            Some(Signals)
         }
     }
 
@@ -164,41 +576,330 @@ impl Signals {
     ///
     /// Note: Dropping the signal handler doesn't automatically unsubscribe.
     /// To return to the default behavior, one has to explicitly call `unsubscribe`.
-    pub fn subscribe(&self, sig: Signal) {
-        unsafe { signal(sig as c_int, Some(handler)); }
+    ///
+    /// This is a thin wrapper around `subscribe_with` using `SigActionFlags::new()`,
+    /// i.e. `SA_RESTART` on and nothing blocked.
+    pub fn subscribe(&self, sig: Signal) -> io::Result<()> {
+        self.subscribe_with(sig, SigActionFlags::new())
     }
 
-    /// Unsubscribe from a signal.
+    /// Subscribe to a signal via `sigaction`, with explicit control over
+    /// `SA_RESTART`/`SA_NOCLDSTOP` and the set of signals blocked while the
+    /// handler runs.
+    ///
+    /// The C-level handler for `sig` is installed once per signal number, by
+    /// whichever handle subscribes to it first; the previously installed
+    /// `sigaction` is saved so that the last subscriber leaving can restore
+    /// it rather than unconditionally resetting to `SIG_DFL`. A later handle
+    /// subscribing to the same signal with stronger requirements *broadens*
+    /// the installed handler rather than being silently ignored: its blocked
+    /// signals are added to the mask, and `SA_RESTART`/`SA_NOCLDSTOP`/
+    /// `origin` (`SA_SIGINFO`) are OR'd in, so e.g. one handle asking for
+    /// `origin: true` after another already subscribed without it still gets
+    /// the sending PID/UID from the kernel.
+    ///
+    /// `flags.origin` also decides which of *this* subscription's channels
+    /// gets fed: with `origin: false` (the default), deliveries for `sig` go
+    /// to `receiver()`; with `origin: true`, they go to `origin_receiver()`
+    /// instead, since `Origin` already carries the decoded `Signal`. Only the
+    /// channel matching what was asked for is ever sent to, so the other one
+    /// doesn't quietly fill up with messages nothing drains.
+    ///
+    /// Returns an error if `sig` is a `Signal::RealTime(n)` whose offset falls
+    /// outside `SIGRTMIN()..=SIGRTMAX()` on this system.
+    pub fn subscribe_with(&self, sig: Signal, flags: SigActionFlags) -> io::Result<()> {
+        let sink = if flags.origin {
+            Sink::Origin(self.origin_snd.clone())
+        } else {
+            Sink::Signal(self.snd.clone())
+        };
+        self.install_handler(sig, flags, Some(sink))
+    }
+
+    // Shared by `subscribe_with` and `subscribe_flag`: installs/broadens the
+    // `sigaction` for `sig` as described above, then registers a recipient
+    // for it. `sink` is the channel to feed on delivery, or `None` for a
+    // `subscribe_flag`-only registration that exists solely to keep the
+    // handler installed and reference-counted.
+    fn install_handler(&self, sig: Signal, flags: SigActionFlags, sink: Option<Sink>) -> io::Result<()> {
+        if let Signal::RealTime(n) = sig {
+            if sigrtmin() + (n as c_int) > sigrtmax() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                           "real-time signal offset exceeds SIGRTMAX()"));
+            }
+        }
+        let raw = signal_to_raw(sig);
+        unsafe {
+            let mut registry = subscribers().lock().unwrap();
+            let mut installed = installed_actions().lock().unwrap();
+
+            let mut mask: sigset_t = zeroed();
+            sigemptyset(&mut mask);
+            let mut sa_flags = 0;
+            let mut needs_siginfo = flags.origin;
+            if let Some(current) = installed.get(&raw) {
+                mask = current.sa_mask;
+                sa_flags = current.sa_flags;
+                needs_siginfo = needs_siginfo || (sa_flags & SA_SIGINFO) != 0;
+                sa_flags &= !SA_SIGINFO;
+            }
+            for blocked in flags.block.iter() {
+                sigaddset(&mut mask, signal_to_raw(*blocked));
+            }
+            if flags.restart {
+                sa_flags |= SA_RESTART;
+            }
+            if flags.no_child_stop {
+                sa_flags |= SA_NOCLDSTOP;
+            }
+
+            let sa_handler = if needs_siginfo {
+                sa_flags |= SA_SIGINFO;
+                handler_siginfo as usize
+            } else {
+                handler as usize
+            };
+            let act = sigaction_t {
+                sa_handler: sa_handler,
+                sa_mask: mask,
+                sa_flags: sa_flags,
+                sa_restorer: 0,
+            };
+
+            if !registry.contains_key(&raw) {
+                let mut old: sigaction_t = zeroed();
+                sigaction(raw, &act, &mut old);
+                old_actions().lock().unwrap().insert(raw, old);
+            } else {
+                sigaction(raw, &act, ptr::null_mut());
+            }
+            installed.insert(raw, act);
+
+            registry.entry(raw).or_insert_with(Vec::new).push(Subscriber {
+                id: self.id,
+                sink: sink,
+            });
+        }
+        self.subscribed.lock().unwrap().push(raw);
+        Ok(())
+    }
+
+    /// Unsubscribe from a signal: remove this handle from the recipient list
+    /// for `sig`. Once the last handle subscribed to `sig` unsubscribes, the
+    /// `sigaction` that was in place before the first `subscribe`/
+    /// `subscribe_with` is restored (falling back to `SIG_DFL` if none was
+    /// saved).
     pub fn unsubscribe(&self, sig: Signal) {
-        unsafe { signal(sig as c_int, None); }
+        self.remove_subscription(signal_to_raw(sig));
+    }
+
+    fn remove_subscription(&self, raw: c_int) {
+        unsafe {
+            let mut registry = subscribers().lock().unwrap();
+            let last_gone = match registry.get_mut(&raw) {
+                Some(subscribed) => {
+                    subscribed.retain(|s| s.id != self.id);
+                    subscribed.is_empty()
+                }
+                None => false,
+            };
+            if last_gone {
+                registry.remove(&raw);
+                installed_actions().lock().unwrap().remove(&raw);
+                let saved = old_actions().lock().unwrap().remove(&raw);
+                match saved {
+                    Some(old) => { sigaction(raw, &old, ptr::null_mut()); }
+                    None => {
+                        let dfl: sigaction_t = zeroed();
+                        sigaction(raw, &dfl, ptr::null_mut());
+                    }
+                }
+            }
+
+            // Also drop this handle's flags for `raw`, if any, so a flag
+            // registered via `subscribe_flag` doesn't outlive the handle that
+            // registered it.
+            let mut flags = flags_registry().lock().unwrap();
+            if let Some(registered) = flags.get_mut(&raw) {
+                registered.retain(|&(id, _)| id != self.id);
+                if registered.is_empty() {
+                    flags.remove(&raw);
+                }
+            }
+        }
+        self.subscribed.lock().unwrap().retain(|&r| r != raw);
+    }
+
+    /// Subscribe `flag` to `sig`: on delivery, `flag` is set to `true` with a
+    /// single atomic store, no channel or iterator involved. Useful for a
+    /// lock-free shutdown flag that a main loop polls.
+    ///
+    /// This installs the handler for `sig` (reference-counted the same way
+    /// as `subscribe`/`subscribe_with`), but does *not* register a channel
+    /// subscription: `receiver()`/`origin_receiver()` stay empty for `sig`
+    /// unless this handle also calls `subscribe`/`subscribe_with` for it.
+    /// That keeps a flag-only subscriber exactly what the name promises — no
+    /// iterator to drain, no channel quietly filling up behind it. Multiple
+    /// flags may be registered for the same signal at once. `flag` is tagged
+    /// with this handle's id so it is removed again by `unsubscribe`/`Drop`,
+    /// rather than outliving the handle.
+    pub fn subscribe_flag(&self, sig: Signal, flag: Arc<AtomicBool>) -> io::Result<()> {
+        try!(self.install_handler(sig, SigActionFlags::new(), None));
+        unsafe {
+            flags_registry().lock().unwrap()
+                .entry(signal_to_raw(sig)).or_insert_with(Vec::new)
+                .push((self.id, flag));
+        }
+        Ok(())
     }
 
-    /// Create a non-blocking iterator over all received signals.
+    /// Remove `flag` from `sig`'s registered flags. Does not touch channel
+    /// delivery or other flags registered for the same signal.
+    pub fn unsubscribe_flag(&self, sig: Signal, flag: &Arc<AtomicBool>) {
+        unsafe {
+            let mut registry = flags_registry().lock().unwrap();
+            if let Some(registered) = registry.get_mut(&signal_to_raw(sig)) {
+                let target = &**flag as *const AtomicBool;
+                registered.retain(|&(_, ref f)| &**f as *const AtomicBool != target);
+            }
+        }
+    }
+
+    /// Block until one of `set` arrives, without installing any handler.
+    ///
+    /// Blocks `set` on the calling thread with `pthread_sigmask` and then
+    /// calls `sigwait`. This is the right tool for simple control flow like
+    /// "block, wait for `Terminate`, shut down" — no handler thread, no
+    /// channel, nothing racing with the rest of the program.
+    ///
+    /// The calling thread's signal mask is restored to what it was before
+    /// this call on every return path, success or error, so `wait` doesn't
+    /// leave `set` permanently blocked behind it.
+    pub fn wait(&self, set: &[Signal]) -> io::Result<Signal> {
+        unsafe {
+            let mut mask: sigset_t = zeroed();
+            sigemptyset(&mut mask);
+            for sig in set.iter() {
+                sigaddset(&mut mask, signal_to_raw(*sig));
+            }
+            let mut old_mask: sigset_t = zeroed();
+            let ret = pthread_sigmask(SIG_BLOCK, &mask, &mut old_mask);
+            if ret != 0 {
+                return Err(io::Error::from_raw_os_error(ret));
+            }
+            loop {
+                let mut num: c_int = 0;
+                match sigwait(&mask, &mut num) {
+                    0 => match signal_from_num(num) {
+                        Some(sig) => {
+                            pthread_sigmask(SIG_SETMASK, &old_mask, ptr::null_mut());
+                            return Ok(sig);
+                        }
+                        None => continue,
+                    },
+                    EINTR => continue,
+                    err => {
+                        pthread_sigmask(SIG_SETMASK, &old_mask, ptr::null_mut());
+                        return Err(io::Error::from_raw_os_error(err));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Like `wait`, but gives up and returns `Ok(None)` after `dur` (total,
+    /// across any `EINTR` retries) if nothing in `set` arrived.
+    ///
+    /// As with `wait`, the calling thread's signal mask is restored to what
+    /// it was before this call on every return path.
+    pub fn wait_timeout(&self, set: &[Signal], dur: Duration) -> io::Result<Option<Signal>> {
+        unsafe {
+            let mut mask: sigset_t = zeroed();
+            sigemptyset(&mut mask);
+            for sig in set.iter() {
+                sigaddset(&mut mask, signal_to_raw(*sig));
+            }
+            let mut old_mask: sigset_t = zeroed();
+            let ret = pthread_sigmask(SIG_BLOCK, &mask, &mut old_mask);
+            if ret != 0 {
+                return Err(io::Error::from_raw_os_error(ret));
+            }
+            let start = Instant::now();
+            loop {
+                let elapsed = start.elapsed();
+                if elapsed >= dur {
+                    pthread_sigmask(SIG_SETMASK, &old_mask, ptr::null_mut());
+                    return Ok(None);
+                }
+                let remaining = dur - elapsed;
+                let timeout = timespec_t {
+                    tv_sec: remaining.as_secs() as i64,
+                    tv_nsec: remaining.subsec_nanos() as i64,
+                };
+                let num = sigtimedwait(&mask, ptr::null_mut(), &timeout);
+                if num >= 0 {
+                    match signal_from_num(num) {
+                        Some(sig) => {
+                            pthread_sigmask(SIG_SETMASK, &old_mask, ptr::null_mut());
+                            return Ok(Some(sig));
+                        }
+                        None => continue,
+                    }
+                }
+                match errno() {
+                    EAGAIN => {
+                        pthread_sigmask(SIG_SETMASK, &old_mask, ptr::null_mut());
+                        return Ok(None);
+                    }
+                    EINTR => continue,
+                    e => {
+                        pthread_sigmask(SIG_SETMASK, &old_mask, ptr::null_mut());
+                        return Err(io::Error::from_raw_os_error(e));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Create a non-blocking iterator over the signals delivered to this
+    /// handle's `receiver()`. Signals subscribed with `origin: true` go to
+    /// `origin_receiver()` instead and are not yielded here.
     pub fn iter<'a>(&'a self) -> SignalIter<'a> {
-        SignalIter
+        SignalIter { rcv: &self.rcv }
     }
 
-    /// Return a reference to the internal `Receiver`.
+    /// Return a reference to this handle's `Receiver`.
     pub fn receiver<'a>(&'a self) -> &'a Receiver<Signal> {
-        unsafe { transmute(RCV) }
+        &self.rcv
+    }
+
+    /// Return a reference to this handle's `Receiver<Origin>`, which carries
+    /// the sending PID/UID for signals subscribed with `origin: true`.
+    pub fn origin_receiver<'a>(&'a self) -> &'a Receiver<Origin> {
+        &self.origin_rcv
     }
 }
 
 impl Drop for Signals {
     fn drop(&mut self) {
-        unsafe { ALIVE.store(false, Relaxed); }
+        let subscribed = self.subscribed.lock().unwrap().clone();
+        for raw in subscribed {
+            self.remove_subscription(raw);
+        }
     }
 }
 
-/// Non-blocking iterator over the available signals.
-pub struct SignalIter<'a>;
+/// Non-blocking iterator over the signals delivered to a `Signals` handle's
+/// `receiver()`.
+pub struct SignalIter<'a> {
+    rcv: &'a Receiver<Signal>,
+}
 
 impl<'a> Iterator for SignalIter<'a> {
     type Item = Signal;
 
     fn next(&mut self) -> Option<Signal> {
-        let rcv: &Receiver<Signal> = unsafe { transmute(RCV) };
-        match rcv.try_recv() {
+        match self.rcv.try_recv() {
             Ok(v) => Some(v),
             _ => None,
         }